@@ -0,0 +1,204 @@
+use anyhow::{anyhow, Context, Result};
+use signature::Signer as _;
+use ssh_key::private::KeypairData;
+use ssh_key::PrivateKey;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{UnixListener, UnixStream};
+use tokio::task::JoinHandle;
+use tracing::{debug, error, warn};
+
+// ssh-agent wire protocol message types we care about (RFC draft-miller-ssh-agent).
+const SSH2_AGENTC_REQUEST_IDENTITIES: u8 = 11;
+const SSH2_AGENT_IDENTITIES_ANSWER: u8 = 12;
+const SSH2_AGENTC_SIGN_REQUEST: u8 = 13;
+const SSH2_AGENT_SIGN_RESPONSE: u8 = 14;
+const SSH_AGENT_FAILURE: u8 = 5;
+
+/// Largest frame we'll allocate for an incoming request. Real `ssh-agent`
+/// implementations cap well under this; anything bigger is a malformed or
+/// hostile frame, not a legitimate sign/list request.
+const MAX_MESSAGE_LEN: usize = 256 * 1024;
+
+/// A minimal in-process ssh-agent that serves a single identity over a Unix
+/// domain socket, so decrypted private key material never touches disk.
+///
+/// The socket lives inside a mode-0700 directory private to this process
+/// (mirroring what `ssh-agent` itself does under `/tmp/ssh-XXXXXX`), so other
+/// local users can't connect to it and turn it into a signing oracle.
+///
+/// The agent lives for the duration of the session: dropping it aborts the
+/// accept loop and removes the socket and its directory.
+pub struct SshAgent {
+    dir: PathBuf,
+    socket_path: PathBuf,
+    task: JoinHandle<()>,
+}
+
+impl SshAgent {
+    /// Spawns the agent as a tokio task and starts listening immediately.
+    pub async fn spawn(key: PrivateKey) -> Result<Self> {
+        let dir = std::env::temp_dir().join(format!("pax-agent-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir(&dir)
+            .with_context(|| format!("Failed to create agent directory at {:?}", dir))?;
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            std::fs::set_permissions(&dir, std::fs::Permissions::from_mode(0o700))
+                .with_context(|| format!("Failed to lock down agent directory at {:?}", dir))?;
+        }
+
+        let socket_path = dir.join("agent.sock");
+        let listener = UnixListener::bind(&socket_path)
+            .with_context(|| format!("Failed to bind agent socket at {:?}", socket_path))?;
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            std::fs::set_permissions(&socket_path, std::fs::Permissions::from_mode(0o600))
+                .with_context(|| format!("Failed to lock down agent socket at {:?}", socket_path))?;
+        }
+
+        let key = Arc::new(key);
+        let cleanup_dir = dir.clone();
+        let task = tokio::spawn(async move {
+            loop {
+                match listener.accept().await {
+                    Ok((stream, _)) => {
+                        let key = Arc::clone(&key);
+                        tokio::spawn(async move {
+                            if let Err(e) = handle_connection(stream, key).await {
+                                debug!("Agent connection closed: {}", e);
+                            }
+                        });
+                    }
+                    Err(e) => {
+                        error!("Agent socket accept failed: {}", e);
+                        break;
+                    }
+                }
+            }
+            let _ = std::fs::remove_dir_all(&cleanup_dir);
+        });
+
+        Ok(Self { dir, socket_path, task })
+    }
+
+    /// Path to the Unix socket; feed this to `SSH_AUTH_SOCK`.
+    pub fn socket_path(&self) -> &Path {
+        &self.socket_path
+    }
+}
+
+impl Drop for SshAgent {
+    fn drop(&mut self) {
+        self.task.abort();
+        let _ = std::fs::remove_dir_all(&self.dir);
+    }
+}
+
+async fn handle_connection(mut stream: UnixStream, key: Arc<PrivateKey>) -> Result<()> {
+    loop {
+        let mut len_buf = [0u8; 4];
+        if stream.read_exact(&mut len_buf).await.is_err() {
+            return Ok(()); // client disconnected
+        }
+        let len = u32::from_be_bytes(len_buf) as usize;
+        if len > MAX_MESSAGE_LEN {
+            warn!("Agent request frame too large ({} bytes), dropping connection", len);
+            return Err(anyhow!("Agent request frame too large: {} bytes", len));
+        }
+
+        let mut body = vec![0u8; len];
+        stream.read_exact(&mut body).await?;
+
+        let msg_type = *body.first().ok_or_else(|| anyhow!("Empty agent message"))?;
+        let payload = &body[1..];
+
+        let reply = match msg_type {
+            SSH2_AGENTC_REQUEST_IDENTITIES => build_identities_answer(&key)?,
+            SSH2_AGENTC_SIGN_REQUEST => build_sign_response(&key, payload).unwrap_or_else(|e| {
+                // Reply with SSH_AGENT_FAILURE instead of dropping the
+                // connection, so the client (and our own logs) see *why*
+                // signing failed rather than a bare EOF.
+                warn!("Agent sign request failed: {}", e);
+                vec![SSH_AGENT_FAILURE]
+            }),
+            other => {
+                warn!("Unsupported agent request type {}", other);
+                vec![SSH_AGENT_FAILURE]
+            }
+        };
+
+        let mut frame = (reply.len() as u32).to_be_bytes().to_vec();
+        frame.extend_from_slice(&reply);
+        stream.write_all(&frame).await?;
+    }
+}
+
+fn build_identities_answer(key: &PrivateKey) -> Result<Vec<u8>> {
+    let blob = key
+        .public_key()
+        .to_bytes()
+        .context("Failed to encode public key blob")?;
+    let comment = key.comment();
+
+    let mut reply = vec![SSH2_AGENT_IDENTITIES_ANSWER];
+    reply.extend_from_slice(&1u32.to_be_bytes()); // we only ever serve one identity
+    put_string(&mut reply, &blob);
+    put_string(&mut reply, comment.as_bytes());
+    Ok(reply)
+}
+
+fn build_sign_response(key: &PrivateKey, payload: &[u8]) -> Result<Vec<u8>> {
+    let mut cursor = payload;
+    let _key_blob = read_string(&mut cursor)?; // echoed back by the client, ignored
+    let data = read_string(&mut cursor)?;
+
+    let (algorithm, signature) = sign_raw(key, data)?;
+
+    let mut sig_blob = Vec::new();
+    put_string(&mut sig_blob, algorithm.as_bytes());
+    put_string(&mut sig_blob, &signature);
+
+    let mut reply = vec![SSH2_AGENT_SIGN_RESPONSE];
+    put_string(&mut reply, &sig_blob);
+    Ok(reply)
+}
+
+/// Signs `data` with the in-memory key, returning the SSH signature-format
+/// algorithm name and the raw signature bytes.
+fn sign_raw(key: &PrivateKey, data: &[u8]) -> Result<(String, Vec<u8>)> {
+    match key.key_data() {
+        KeypairData::Ed25519(pair) => {
+            let signing_key = ed25519_dalek::SigningKey::from_bytes(&pair.private.to_bytes());
+            let sig: ed25519_dalek::Signature = signing_key.sign(data);
+            Ok(("ssh-ed25519".to_string(), sig.to_bytes().to_vec()))
+        }
+        _ => Err(anyhow!(
+            "Signing is currently only supported for ed25519 keys"
+        )),
+    }
+}
+
+fn put_string(buf: &mut Vec<u8>, s: &[u8]) {
+    buf.extend_from_slice(&(s.len() as u32).to_be_bytes());
+    buf.extend_from_slice(s);
+}
+
+fn read_string<'a>(cursor: &mut &'a [u8]) -> Result<&'a [u8]> {
+    if cursor.len() < 4 {
+        return Err(anyhow!("Truncated agent message"));
+    }
+    let (len_bytes, rest) = cursor.split_at(4);
+    let len = u32::from_be_bytes(len_bytes.try_into().unwrap()) as usize;
+    if rest.len() < len {
+        return Err(anyhow!("Truncated agent message"));
+    }
+    let (value, rest) = rest.split_at(len);
+    *cursor = rest;
+    Ok(value)
+}
@@ -4,10 +4,9 @@ use clap::Parser;
 use colored::*;
 use reqwest::Client;
 use serde::Deserialize;
-use std::io::Write;
+use ssh_key::PrivateKey;
 use std::path::PathBuf;
 use std::time::Duration;
-use tempfile::NamedTempFile;
 use tracing::info;
 
 #[derive(Parser, Debug, Clone)]
@@ -44,8 +43,21 @@ pub struct AppArgs {
     #[arg(short = 'k', long)]
     pub private_key: Option<String>,
 
+    /// Expected SHA256 fingerprint of the server's host key (pins the
+    /// connection instead of trusting on first use)
+    #[arg(long)]
+    pub host_key_fingerprint: Option<String>,
+
+    /// Path to a known_hosts file to verify the server against
+    #[arg(long)]
+    pub known_hosts: Option<String>,
+
     // --- Common Settings ---
 
+    /// Local bind address for the SOCKS5 listener
+    #[arg(long, default_value = "127.0.0.1")]
+    pub local_host: String,
+
     /// Local SOCKS5 port
     #[arg(short, long, default_value = "1080")]
     pub local_port: u16,
@@ -78,6 +90,11 @@ pub struct SshConfig {
     pub password: Option<String>,
     pub private_key: Option<String>,
 
+    // Host key verification: pin an exact fingerprint, or point at a
+    // known_hosts file; if both are absent, the runner falls back to TOFU.
+    pub host_key_fingerprint: Option<String>,
+    pub known_hosts: Option<String>,
+
     pub exp_at: Option<String>,
 }
 
@@ -85,7 +102,7 @@ fn default_port() -> String { "22".to_string() }
 fn default_auth_type() -> AuthType { AuthType::Password }
 
 /// Helper: Prints the node information visually.
-pub fn print_node_info(config: &SshConfig) {
+pub fn print_node_info(config: &SshConfig, key_info: Option<&KeyInfo>) {
     let region_display = config.region.as_deref().unwrap_or("UNK");
 
     println!();
@@ -105,6 +122,20 @@ pub fn print_node_info(config: &SshConfig) {
     if let Some(ref r) = config.ref_info {
         println!("{} {}", "  -> Ref :".bold(), r.blue().underline());
     }
+
+    if let Some(ref fp) = config.host_key_fingerprint {
+        println!("{} {}", "  -> Pin :".bold(), fp.magenta());
+    }
+
+    if let Some(info) = key_info {
+        let note = if info.encrypted { " (encrypted)" } else { "" };
+        println!("{} {} {}{}",
+            "  -> Key :".bold(),
+            info.algorithm.cyan(),
+            info.fingerprint,
+            note.yellow()
+        );
+    }
     println!();
 
     check_expiration(&config.exp_at);
@@ -130,6 +161,8 @@ pub fn create_from_args(args: &AppArgs) -> Result<SshConfig> {
         ref_info: Some("CLI Args".to_string()),
         password: args.password.clone(),
         private_key: args.private_key.clone(),
+        host_key_fingerprint: args.host_key_fingerprint.clone(),
+        known_hosts: args.known_hosts.clone(),
         exp_at: None,
     };
 
@@ -172,29 +205,76 @@ fn expand_tilde(path_str: &str) -> PathBuf {
     PathBuf::from(path_str)
 }
 
-pub fn prepare_private_key(key_input: &str) -> Result<(String, Option<NamedTempFile>)> {
+fn load_raw_key(key_input: &str) -> Result<String> {
     if key_input.contains("PRIVATE KEY") {
-        let mut temp_file = NamedTempFile::new()?;
-        temp_file.write_all(key_input.as_bytes())?;
-
-        #[cfg(unix)]
-        {
-            use std::os::unix::fs::PermissionsExt;
-            let mut perms = temp_file.as_file().metadata()?.permissions();
-            perms.set_mode(0o600);
-            temp_file.as_file().set_permissions(perms)?;
-        }
-
-        let path = temp_file.path().to_string_lossy().to_string();
-        Ok((path, Some(temp_file)))
+        Ok(key_input.to_string())
     } else {
         let expanded_path = expand_tilde(key_input);
+        std::fs::read_to_string(&expanded_path)
+            .with_context(|| format!("Private key file not found: {} (Expanded: {:?})", key_input, expanded_path))
+    }
+}
 
-        if expanded_path.exists() && expanded_path.is_file() {
-            Ok((expanded_path.to_string_lossy().to_string(), None))
-        } else {
-            Err(anyhow!("Private key file not found: {} (Expanded: {:?})", key_input, expanded_path))
-        }
+/// Algorithm/fingerprint summary of a private key, cheap to compute because
+/// it doesn't require decrypting the key (the public part is never
+/// encrypted).
+pub struct KeyInfo {
+    pub algorithm: String,
+    pub fingerprint: String,
+    pub encrypted: bool,
+}
+
+/// Checks that `key`'s algorithm is one `SshAgent` can actually sign with.
+/// Keeps the "malformed/unsupported key" rejection in one place instead of
+/// letting an unsupported algorithm surface later as a confusing agent
+/// sign failure.
+fn ensure_signable(key: &PrivateKey) -> Result<()> {
+    match key.algorithm() {
+        Ok(ssh_key::Algorithm::Ed25519) => Ok(()),
+        Ok(other) => Err(anyhow!(
+            "Unsupported key algorithm: {} (only ed25519 is currently supported)",
+            other
+        )),
+        Err(e) => Err(anyhow!("Could not determine key algorithm: {}", e)),
+    }
+}
+
+/// Parses (without decrypting) the configured key just enough to classify
+/// it, so unsupported/malformed keys are rejected before anything talks to
+/// the server.
+pub fn inspect_private_key(key_input: &str) -> Result<KeyInfo> {
+    let raw = load_raw_key(key_input)?;
+    let key = PrivateKey::from_openssh(&raw)
+        .context("Failed to parse private key (unsupported or malformed format)")?;
+    ensure_signable(&key)?;
+
+    let algorithm = key
+        .algorithm()
+        .map(|a| a.to_string())
+        .unwrap_or_else(|_| "unknown".to_string());
+
+    Ok(KeyInfo {
+        algorithm,
+        fingerprint: key.fingerprint(ssh_key::HashAlg::Sha256).to_string(),
+        encrypted: key.is_encrypted(),
+    })
+}
+
+/// Loads the raw key material, either an inline PEM-ish string or a path to a
+/// key file, and parses (and decrypts, if needed) it with `ssh-key`.
+///
+/// The result is kept entirely in memory; callers hand it to `SshAgent`
+/// instead of ever writing it to disk.
+pub fn prepare_private_key(key_input: &str, passphrase: Option<&str>) -> Result<PrivateKey> {
+    let raw = load_raw_key(key_input)?;
+    let key = PrivateKey::from_openssh(&raw).context("Failed to parse private key")?;
+    ensure_signable(&key)?;
+
+    if key.is_encrypted() {
+        let pass = passphrase.ok_or_else(|| anyhow!("Passphrase required for encrypted key"))?;
+        key.decrypt(pass).context("Failed to decrypt private key (wrong passphrase?)")
+    } else {
+        Ok(key)
     }
 }
 
@@ -0,0 +1,130 @@
+use anyhow::{anyhow, Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::path::PathBuf;
+use std::process::Command;
+use tracing::info;
+
+/// Trust-on-first-use store for server host key fingerprints, keyed by
+/// `host:port`. Lives at `~/.pax/known_hosts.json`.
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct Store {
+    #[serde(flatten)]
+    fingerprints: HashMap<String, String>,
+}
+
+/// Outcome of comparing a freshly presented fingerprint against the store.
+pub enum TofuResult {
+    /// Never seen this host before; it has now been pinned.
+    FirstSeen,
+    /// Matches the fingerprint pinned on a previous connection.
+    Matched,
+    /// Differs from what was pinned before - possible MITM or a rotated key.
+    Mismatch { previous: String },
+}
+
+fn store_path() -> Result<PathBuf> {
+    let home = dirs::home_dir().context("Could not determine home directory")?;
+    Ok(home.join(".pax").join("known_hosts.json"))
+}
+
+fn load() -> Result<Store> {
+    let path = store_path()?;
+    if !path.exists() {
+        return Ok(Store::default());
+    }
+    let raw = fs::read_to_string(&path)?;
+    Ok(serde_json::from_str(&raw).unwrap_or_default())
+}
+
+fn save(store: &Store) -> Result<()> {
+    let path = store_path()?;
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    fs::write(path, serde_json::to_string_pretty(store)?)?;
+    Ok(())
+}
+
+/// Checks `fingerprint` against what was previously trusted for `host_port`,
+/// pinning it on first sight.
+pub fn check_and_trust(host_port: &str, fingerprint: &str) -> Result<TofuResult> {
+    let mut store = load()?;
+
+    match store.fingerprints.get(host_port) {
+        Some(previous) if previous == fingerprint => Ok(TofuResult::Matched),
+        Some(previous) => Ok(TofuResult::Mismatch { previous: previous.clone() }),
+        None => {
+            store.fingerprints.insert(host_port.to_string(), fingerprint.to_string());
+            save(&store)?;
+            info!("Trusting new host key for {} on first use ({})", host_port, fingerprint);
+            Ok(TofuResult::FirstSeen)
+        }
+    }
+}
+
+/// Verifies a freshly presented host key fingerprint for `host_port`: against
+/// `pinned` if the caller configured one, otherwise against the
+/// trust-on-first-use store. Shared by every backend that needs to decide
+/// whether to trust a presented key, since the pin/TOFU policy itself
+/// doesn't depend on how the key was obtained.
+pub fn verify(host_port: &str, pinned: Option<&str>, fingerprint: &str) -> Result<()> {
+    if let Some(expected) = pinned {
+        if expected == fingerprint {
+            info!("Host key fingerprint matches pinned value.");
+            Ok(())
+        } else {
+            Err(anyhow!(
+                "Host key fingerprint mismatch! Expected {}, got {} (possible MITM)",
+                expected, fingerprint
+            ))
+        }
+    } else {
+        match check_and_trust(host_port, fingerprint)? {
+            TofuResult::FirstSeen | TofuResult::Matched => Ok(()),
+            TofuResult::Mismatch { previous } => Err(anyhow!(
+                "Host key fingerprint changed for {}! Was {}, now {} (possible MITM)",
+                host_port, previous, fingerprint
+            )),
+        }
+    }
+}
+
+/// Looks `host` up in the known_hosts file at `path` (via `ssh-keygen -F`)
+/// and checks `fingerprint` against whatever is on file there. This is the
+/// strict, explicit-known_hosts counterpart to [`verify`]: an unlisted host
+/// is an error, not something to trust on first use, matching what
+/// `UserKnownHostsFile` + `StrictHostKeyChecking=yes` does for the
+/// subprocess backend.
+pub fn verify_against_file(path: &str, host: &str, fingerprint: &str) -> Result<()> {
+    let output = Command::new("ssh-keygen")
+        .arg("-F").arg(host)
+        .arg("-f").arg(path)
+        .arg("-l")
+        .output()
+        .context("Failed to run ssh-keygen")?;
+
+    let text = String::from_utf8_lossy(&output.stdout);
+    let known_fingerprints: Vec<&str> = text
+        .lines()
+        .filter(|l| !l.starts_with('#'))
+        .filter_map(|l| l.split_whitespace().nth(1))
+        .collect();
+
+    if known_fingerprints.is_empty() {
+        return Err(anyhow!(
+            "Host {} not found in known_hosts file {}",
+            host, path
+        ));
+    }
+
+    if known_fingerprints.iter().any(|known| *known == fingerprint) {
+        Ok(())
+    } else {
+        Err(anyhow!(
+            "Host key fingerprint mismatch for {} against {}: got {} (possible MITM)",
+            host, path, fingerprint
+        ))
+    }
+}
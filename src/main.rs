@@ -1,12 +1,33 @@
+mod agent;
 mod config;
+mod host_keys;
+#[cfg(feature = "native-ssh")]
+mod native_runner;
 mod runner;
 
 use clap::Parser;
-use std::time::Duration;
+use rand::Rng;
+use std::time::{Duration, Instant};
 use tokio::signal;
 use tracing::{error, info, Level};
 use tracing_subscriber::EnvFilter;
 
+/// Starting point for the reconnect backoff.
+const BACKOFF_BASE: Duration = Duration::from_secs(5);
+/// Upper bound for the reconnect backoff.
+const BACKOFF_CAP: Duration = Duration::from_secs(300);
+/// A session that stays up at least this long resets the backoff counter.
+const STABILITY_THRESHOLD: Duration = Duration::from_secs(30);
+
+/// Full-jitter exponential backoff: `rand(0, min(cap, base * 2^attempt))`.
+fn full_jitter_backoff(attempt: u32) -> Duration {
+    let multiplier = 1u64.checked_shl(attempt).unwrap_or(u64::MAX);
+    let exp_ms = (BACKOFF_BASE.as_millis() as u64).saturating_mul(multiplier);
+    let capped_ms = exp_ms.min(BACKOFF_CAP.as_millis() as u64);
+    let jitter_ms = rand::thread_rng().gen_range(0..=capped_ms);
+    Duration::from_millis(jitter_ms)
+}
+
 #[tokio::main]
 async fn main() {
     // Setup logging with ANSI color support
@@ -30,7 +51,11 @@ async fn main() {
         info!("Mode: API Fetch (Target: {})", args.api);
     }
 
+    let mut attempt = 0u32;
+
     loop {
+        let session_started = Instant::now();
+
         if let Err(e) = run_session(&args).await {
             error!("Session ended: {:?}", e);
 
@@ -41,8 +66,18 @@ async fn main() {
             }
         }
 
-        info!("Reconnecting in 5 seconds...");
-        tokio::time::sleep(Duration::from_secs(5)).await;
+        // A session that actually stayed connected past the stability
+        // threshold means the endpoint is healthy; don't keep penalizing it
+        // for a past streak of failures.
+        if session_started.elapsed() >= STABILITY_THRESHOLD {
+            attempt = 0;
+        } else {
+            attempt = attempt.saturating_add(1);
+        }
+
+        let backoff = full_jitter_backoff(attempt);
+        info!("Reconnecting in {:.1}s (attempt {})...", backoff.as_secs_f64(), attempt);
+        tokio::time::sleep(backoff).await;
     }
 }
 
@@ -67,33 +102,52 @@ async fn run_session(args: &config::AppArgs) -> anyhow::Result<()> {
         }
     }
 
-    // 3. Display Config (Unified visualization)
-    config::print_node_info(&ssh_cfg);
+    // 3. Classify the key up front (cheap: doesn't require the passphrase) so
+    // malformed/unsupported keys are rejected before we even display the node,
+    // and so the detected identity shows up in that display.
+    let key_info = if ssh_cfg.auth_type == config::AuthType::Key {
+        let raw_key = ssh_cfg
+            .private_key
+            .as_ref()
+            .ok_or_else(|| anyhow::anyhow!("AuthType is Key but no key provided."))?;
+        Some(config::inspect_private_key(raw_key)?)
+    } else {
+        None
+    };
+
+    // 4. Display Config (Unified visualization)
+    config::print_node_info(&ssh_cfg, key_info.as_ref());
 
-    // 4. Prepare Private Key (Temp file or Local path)
-    let _key_guard: Option<tempfile::NamedTempFile>;
+    // 5. Prepare Private Key (in-memory, via an in-process ssh-agent)
+    let _ssh_agent: Option<agent::SshAgent>;
 
     if ssh_cfg.auth_type == config::AuthType::Key {
         if let Some(ref raw_key) = ssh_cfg.private_key {
-            let (final_path, guard) = config::prepare_private_key(raw_key)?;
-            ssh_cfg.private_key = Some(final_path);
-            _key_guard = guard;
+            let private_key = config::prepare_private_key(raw_key, ssh_cfg.password.as_deref())?;
+            _ssh_agent = Some(agent::SshAgent::spawn(private_key).await?);
         } else {
             return Err(anyhow::anyhow!("AuthType is Key but no key provided."));
         }
     } else {
-        _key_guard = None;
+        _ssh_agent = None;
     }
 
+    let auth_sock = _ssh_agent.as_ref().map(|a| a.socket_path().to_path_buf());
     let port = args.local_port;
     let host = args.local_host.clone();
     let cfg_clone = ssh_cfg.clone();
 
-    // 5. Run SSH with Signal Handling
+    // 6. Run SSH with Signal Handling
     tokio::select! {
         res = tokio::task::spawn_blocking(move || {
-            runner::start_ssh_process(&host, port, &cfg_clone)
-
+            #[cfg(feature = "native-ssh")]
+            {
+                native_runner::start_native_ssh_process(&host, port, &cfg_clone, auth_sock.as_deref())
+            }
+            #[cfg(not(feature = "native-ssh"))]
+            {
+                runner::start_ssh_process(&host, port, &cfg_clone, auth_sock.as_deref())
+            }
         }) => {
             match res {
                 Ok(inner) => inner,
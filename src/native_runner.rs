@@ -0,0 +1,207 @@
+use crate::config::{AuthType, SshConfig};
+use crate::host_keys;
+use anyhow::{anyhow, Context, Result};
+use std::io::{Read, Write};
+use std::net::{Ipv4Addr, Ipv6Addr, TcpListener, TcpStream};
+use std::path::Path;
+use std::time::Duration;
+use tracing::{debug, info, warn};
+use wezterm_ssh::{Config, Session, SessionEvent};
+
+/// Native backend: establishes the SSH session itself (no `ssh` binary) and
+/// implements the SOCKS5 server in-process instead of shelling out to `-D`.
+///
+/// Enabled by the `native-ssh` feature; the default backend remains
+/// `runner::start_ssh_process`, which drives the system `ssh` client.
+pub fn start_native_ssh_process(
+    local_host: &str,
+    local_port: u16,
+    config: &SshConfig,
+    auth_sock: Option<&Path>,
+) -> Result<()> {
+    let session = connect(config, auth_sock)?;
+
+    let listener = TcpListener::bind((local_host, local_port))
+        .with_context(|| format!("Failed to bind SOCKS5 listener on {}:{}", local_host, local_port))?;
+    info!("Native SOCKS5 proxy listening on {}:{}", local_host, local_port);
+
+    for incoming in listener.incoming() {
+        let client = incoming?;
+        let session = session.clone();
+        std::thread::spawn(move || {
+            if let Err(e) = handle_client(client, session) {
+                warn!("SOCKS5 client error: {}", e);
+            }
+        });
+    }
+
+    Ok(())
+}
+
+/// Opens the SSH session and authenticates natively against `config`.
+///
+/// When `auth_sock` is set, it points at the in-process `SshAgent`'s socket
+/// (see `agent.rs`); it's handed to the library as `IdentityAgent` so key
+/// auth is delegated to the agent instead of touching key material here,
+/// the same division of responsibility the subprocess backend uses.
+fn connect(config: &SshConfig, auth_sock: Option<&Path>) -> Result<Session> {
+    let mut ssh_config = Config::new();
+    ssh_config.add_default_config_files();
+
+    let mut opts = ssh_config.for_host(&config.host);
+    opts.insert("user".to_string(), config.user.clone());
+    opts.insert("port".to_string(), config.port.clone());
+
+    if config.auth_type == AuthType::Key {
+        let sock = auth_sock.ok_or_else(|| anyhow!("AuthType is Key but no agent socket was provided."))?;
+        let sock = sock.to_str().ok_or_else(|| anyhow!("Non-UTF8 agent socket path"))?;
+        debug!("Using in-process ssh-agent at {}", sock);
+        opts.insert("identityagent".to_string(), sock.to_string());
+    }
+
+    let (session, events) = Session::connect(opts).context("Failed to start SSH session")?;
+    let host_port = format!("{}:{}", config.host, config.port);
+    // Bracket notation matches what ssh-keygen/ssh use for non-default ports
+    // in known_hosts lookups, e.g. "[host]:2222".
+    let known_hosts_lookup_host = if config.port == "22" {
+        config.host.clone()
+    } else {
+        format!("[{}]:{}", config.host, config.port)
+    };
+
+    for event in events.iter() {
+        match event {
+            SessionEvent::Banner(_) => {}
+            SessionEvent::HostVerify(verify) => {
+                let fingerprint = verify.key.fingerprint(ssh_key::HashAlg::Sha256).to_string();
+                let result = if let Some(ref known_hosts) = config.known_hosts {
+                    host_keys::verify_against_file(known_hosts, &known_hosts_lookup_host, &fingerprint)
+                } else {
+                    host_keys::verify(&host_port, config.host_key_fingerprint.as_deref(), &fingerprint)
+                };
+                match result {
+                    Ok(()) => {
+                        let _ = verify.answer(true);
+                    }
+                    Err(e) => {
+                        let _ = verify.answer(false);
+                        return Err(e);
+                    }
+                }
+            }
+            SessionEvent::Authenticate(auth) => match config.auth_type {
+                AuthType::Password => {
+                    let password = config
+                        .password
+                        .as_deref()
+                        .ok_or_else(|| anyhow!("Server asked for password but none provided!"))?;
+                    let _ = auth.answer(vec![password.to_string()]);
+                }
+                AuthType::Key => {
+                    // The agent configured above already attempted public-key
+                    // auth; reaching here means it was rejected and the server
+                    // is asking for something we have no fallback for.
+                    return Err(anyhow!(
+                        "Key authentication via agent was rejected and no interactive fallback is available"
+                    ));
+                }
+            },
+            SessionEvent::Error(e) => return Err(anyhow!("SSH session error: {}", e)),
+            SessionEvent::Authenticated => break,
+        }
+    }
+
+    Ok(session)
+}
+
+fn handle_client(mut client: TcpStream, session: Session) -> Result<()> {
+    // --- Greeting ---
+    let mut header = [0u8; 2];
+    client.read_exact(&mut header)?;
+    let nmethods = header[1] as usize;
+    let mut methods = vec![0u8; nmethods];
+    client.read_exact(&mut methods)?;
+    client.write_all(&[0x05, 0x00])?; // no-auth
+
+    // --- CONNECT request ---
+    let mut req_header = [0u8; 4];
+    client.read_exact(&mut req_header)?;
+    let (version, cmd, _rsv, atyp) = (req_header[0], req_header[1], req_header[2], req_header[3]);
+    if version != 0x05 || cmd != 0x01 {
+        return Err(anyhow!("Unsupported SOCKS5 request (version={}, cmd={})", version, cmd));
+    }
+
+    let dst_addr = match atyp {
+        0x01 => {
+            let mut buf = [0u8; 4];
+            client.read_exact(&mut buf)?;
+            Ipv4Addr::from(buf).to_string()
+        }
+        0x03 => {
+            let mut len = [0u8; 1];
+            client.read_exact(&mut len)?;
+            let mut name = vec![0u8; len[0] as usize];
+            client.read_exact(&mut name)?;
+            String::from_utf8(name).context("Invalid domain name in SOCKS5 request")?
+        }
+        0x04 => {
+            let mut buf = [0u8; 16];
+            client.read_exact(&mut buf)?;
+            Ipv6Addr::from(buf).to_string()
+        }
+        other => return Err(anyhow!("Unsupported SOCKS5 address type: {}", other)),
+    };
+
+    let mut port_buf = [0u8; 2];
+    client.read_exact(&mut port_buf)?;
+    let dst_port = u16::from_be_bytes(port_buf);
+
+    debug!("SOCKS5 CONNECT -> {}:{}", dst_addr, dst_port);
+
+    let channel = session
+        .open_direct_tcpip(&dst_addr, dst_port, ("0.0.0.0", 0))
+        .context("Failed to open direct-tcpip channel")?;
+
+    // BND.ADDR/BND.PORT are advisory; SOCKS5 clients generally ignore them.
+    client.write_all(&[0x05, 0x00, 0x00, 0x01, 0, 0, 0, 0, 0, 0])?;
+
+    pump(client, channel)
+}
+
+/// Polls both directions and copies whatever is ready. Avoids needing a
+/// clonable duplex handle for the channel, at the cost of a short sleep when
+/// both sides are idle.
+fn pump(mut client: TcpStream, mut channel: impl Read + Write) -> Result<()> {
+    client.set_nonblocking(true)?;
+
+    let mut client_buf = [0u8; 8192];
+    let mut chan_buf = [0u8; 8192];
+
+    loop {
+        let mut activity = false;
+
+        match client.read(&mut client_buf) {
+            Ok(0) => return Ok(()),
+            Ok(n) => {
+                channel.write_all(&client_buf[..n])?;
+                activity = true;
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::WouldBlock => {}
+            Err(e) => return Err(e.into()),
+        }
+
+        match channel.read(&mut chan_buf) {
+            Ok(0) => return Ok(()),
+            Ok(n) => {
+                client.write_all(&chan_buf[..n])?;
+                activity = true;
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::WouldBlock => {}
+            Err(e) => return Err(e.into()),
+        }
+
+        if !activity {
+            std::thread::sleep(Duration::from_millis(5));
+        }
+    }
+}
@@ -1,26 +1,152 @@
 use crate::config::{SshConfig, AuthType};
-use anyhow::{anyhow, Result};
+use crate::host_keys;
+use anyhow::{anyhow, Context, Result};
 use expectrl::{Eof, Regex, Session};
+use std::collections::VecDeque;
+use std::io::Write as _;
+use std::path::Path;
 use std::process::Command;
+use std::time::Duration;
+use tempfile::NamedTempFile;
 use tracing::{info, warn, debug};
 
+/// Fetches the server's host key(s) in known_hosts format via `ssh-keyscan`,
+/// independently of the main `ssh` invocation.
+fn keyscan(host: &str, port: &str) -> Result<String> {
+    let output = Command::new("ssh-keyscan")
+        .arg("-p").arg(port)
+        .arg(host)
+        .output()
+        .context("Failed to run ssh-keyscan")?;
+
+    if output.stdout.is_empty() {
+        return Err(anyhow!(
+            "ssh-keyscan returned no host key for {}:{} ({})",
+            host, port,
+            String::from_utf8_lossy(&output.stderr)
+        ));
+    }
+
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+/// Computes the SHA256 fingerprint of a known_hosts-format key line via
+/// `ssh-keygen -lf -`.
+fn fingerprint_of(known_hosts_line: &str) -> Result<String> {
+    let mut child = Command::new("ssh-keygen")
+        .arg("-lf").arg("-")
+        .stdin(std::process::Stdio::piped())
+        .stdout(std::process::Stdio::piped())
+        .spawn()
+        .context("Failed to run ssh-keygen")?;
+
+    child
+        .stdin
+        .take()
+        .ok_or_else(|| anyhow!("ssh-keygen stdin unavailable"))?
+        .write_all(known_hosts_line.as_bytes())?;
+
+    let output = child.wait_with_output()?;
+    let text = String::from_utf8_lossy(&output.stdout);
+    text.split_whitespace()
+        .nth(1)
+        .map(|s| s.to_string())
+        .ok_or_else(|| anyhow!("Could not parse ssh-keygen fingerprint output: {}", text))
+}
+
+/// Verifies the server's host key *before* the real `ssh` process ever
+/// starts talking to it, and returns a `known_hosts` file pinned to exactly
+/// that key. This is what lets us pass `StrictHostKeyChecking=yes` instead
+/// of trusting blindly and only noticing a mismatch after the fact: ssh
+/// itself refuses the handshake if the server doesn't present this key.
+fn verify_and_pin_host_key(config: &SshConfig) -> Result<NamedTempFile> {
+    let scanned = keyscan(&config.host, &config.port)?;
+    let key_line = scanned
+        .lines()
+        .find(|l| !l.trim().is_empty() && !l.starts_with('#'))
+        .ok_or_else(|| anyhow!("ssh-keyscan returned no host key for {}:{}", config.host, config.port))?;
+    let fingerprint = fingerprint_of(key_line)?;
+
+    let host_port = format!("{}:{}", config.host, config.port);
+    host_keys::verify(&host_port, config.host_key_fingerprint.as_deref(), &fingerprint)?;
+
+    let mut known_hosts = NamedTempFile::new()?;
+    writeln!(known_hosts, "{}", key_line)?;
+    Ok(known_hosts)
+}
+
+/// How many lines of `ssh -v` output to retain for error reporting.
+const LOG_BUFFER_LINES: usize = 200;
+
+/// Give up waiting for a state transition after this many silent 5s beats.
+const MAX_SILENT_BEATS: u32 = 6;
+
+/// Retains the last [`LOG_BUFFER_LINES`] lines of ssh's verbose output so a
+/// failure can be reported with the diagnostics that actually explain it,
+/// instead of just "timed out".
+struct LogBuffer {
+    lines: VecDeque<String>,
+}
+
+impl LogBuffer {
+    fn new() -> Self {
+        Self { lines: VecDeque::with_capacity(LOG_BUFFER_LINES) }
+    }
+
+    fn push(&mut self, chunk: &str) {
+        for line in chunk.lines().filter(|l| !l.trim().is_empty()) {
+            if self.lines.len() == LOG_BUFFER_LINES {
+                self.lines.pop_front();
+            }
+            self.lines.push_back(line.to_string());
+        }
+    }
+
+    fn dump(&self) -> String {
+        self.lines.iter().cloned().collect::<Vec<_>>().join("\n")
+    }
+}
+
 /// Starts the SSH process using `expectrl`.
-pub fn start_ssh_process(local_port: u16, config: &SshConfig) -> Result<()> {
+///
+/// When `auth_sock` is set, it is exported as `SSH_AUTH_SOCK` so `ssh`
+/// authenticates through the in-process agent instead of `-i`; the key
+/// material itself never touches this function.
+pub fn start_ssh_process(
+    local_host: &str,
+    local_port: u16,
+    config: &SshConfig,
+    auth_sock: Option<&Path>,
+) -> Result<()> {
+    // Resolve and verify the host key *before* spawning ssh, so a mismatch
+    // stops us before authentication starts rather than racing it.
+    let synthesized_known_hosts;
+    let known_hosts_path: &str = match config.known_hosts {
+        Some(ref path) => path,
+        None => {
+            synthesized_known_hosts = verify_and_pin_host_key(config)?;
+            synthesized_known_hosts.path().to_str().ok_or_else(|| anyhow!("Non-UTF8 known_hosts path"))?
+        }
+    };
+
     let mut cmd = Command::new("ssh");
 
-    cmd.arg("-D").arg(local_port.to_string())
+    cmd.arg("-D").arg(format!("{}:{}", local_host, local_port))
        .arg("-N") // No remote command (forwarding only)
        .arg("-C") // Compression
        .arg("-v") // Verbose (helps debugging, but we rely on process state)
-       .arg("-o").arg("StrictHostKeyChecking=no")
-       .arg("-o").arg("UserKnownHostsFile=/dev/null")
        .arg("-o").arg("ServerAliveInterval=15")
-       .arg("-o").arg("ConnectTimeout=10");
+       .arg("-o").arg("ConnectTimeout=10")
+       .arg("-o").arg("StrictHostKeyChecking=yes")
+       .arg("-o").arg(format!("UserKnownHostsFile={}", known_hosts_path));
 
     if config.auth_type == AuthType::Key {
-        if let Some(ref key_path) = config.private_key {
-            debug!("Using private key path: {}", key_path);
-            cmd.arg("-i").arg(key_path);
+        match auth_sock {
+            Some(sock) => {
+                debug!("Using in-process ssh-agent at {:?}", sock);
+                cmd.env("SSH_AUTH_SOCK", sock);
+            }
+            None => return Err(anyhow!("AuthType is Key but no agent socket was provided.")),
         }
     }
 
@@ -30,20 +156,43 @@ pub fn start_ssh_process(local_port: u16, config: &SshConfig) -> Result<()> {
     info!("Executing SSH process...");
 
     let mut p = Session::spawn(cmd).map_err(|e| anyhow!("Failed to spawn SSH: {}", e))?;
+    let mut log = LogBuffer::new();
 
     // --- INTERACTION PHASE ---
-    // We give SSH a few seconds to prompt for password or fail.
-    // If it says nothing for 5 seconds but stays alive, we assume success.
-    p.set_expect_timeout(Some(std::time::Duration::from_secs(5)));
+    // Watch the -v output for real state transitions (auth succeeded, tunnel
+    // up, auth exhausted) instead of guessing success from silence.
+    p.set_expect_timeout(Some(Duration::from_secs(5)));
+    let mut silent_beats = 0u32;
 
     loop {
-        // Watch for specific prompts or errors
-        let result = p.expect(Regex("password:|Enter passphrase|Connection refused|timed out|denied"));
+        let result = p.expect(Regex(
+            "password:|Enter passphrase|Connection refused|timed out|denied|\
+             Host key verification failed|\
+             Authentication succeeded|\
+             No more authentication methods|Local connections to .* forwarded to remote|\
+             Entering interactive session",
+        ));
 
         match result {
             Ok(output) => {
-                let match_str = String::from_utf8_lossy(output.get(0).unwrap_or(&[]));
-                let buf_str = String::from_utf8_lossy(output.before());
+                silent_beats = 0;
+                let match_str = String::from_utf8_lossy(output.get(0).unwrap_or(&[])).into_owned();
+                let buf_str = String::from_utf8_lossy(output.before()).into_owned();
+                log.push(&buf_str);
+                log.push(&match_str);
+
+                // Without a configured known_hosts file we already pinned and
+                // verified the host key in `verify_and_pin_host_key` before
+                // spawning ssh, and this is just the backstop in case ssh's
+                // own check against that pinned entry still trips. With a
+                // configured known_hosts file, this is where ssh reports that
+                // the server's key doesn't match it.
+                if match_str.contains("Host key verification failed") {
+                    return Err(anyhow!(
+                        "Host key verification failed against the pinned key (possible MITM):\n{}",
+                        log.dump()
+                    ));
+                }
 
                 // 1. Password Prompt
                 if match_str.contains("password:") {
@@ -72,27 +221,58 @@ pub fn start_ssh_process(local_port: u16, config: &SshConfig) -> Result<()> {
                     }
                 }
 
-                // 3. Explicit Errors
-                if buf_str.contains("Connection refused") || buf_str.contains("timed out") {
-                    return Err(anyhow!("Connection failed (Refused/Timeout)"));
+                // 3. Real success signals
+                if match_str.contains("Local connections to") || match_str.contains("Entering interactive session") {
+                    info!("Tunnel established. SOCKS5: {}:{}", local_host, local_port);
+                    break;
                 }
-                if buf_str.contains("denied") {
-                    return Err(anyhow!("Permission denied (Wrong password/key?)"));
+                if match_str.contains("Authentication succeeded") {
+                    info!("Authentication succeeded, waiting for the tunnel to come up...");
+                    continue;
+                }
+
+                // 4. Terminal failures
+                //
+                // Note: "Authentications that can continue" is deliberately
+                // *not* treated as terminal here - OpenSSH prints it after
+                // every rejected `none` auth probe, on every connection,
+                // before the real auth method is even attempted.
+                if match_str.contains("No more authentication methods") {
+                    return Err(anyhow!(
+                        "SSH exhausted all authentication methods:\n{}",
+                        log.dump()
+                    ));
+                }
+                if match_str.contains("Connection refused") || match_str.contains("timed out") {
+                    return Err(anyhow!("Connection failed (Refused/Timeout):\n{}", log.dump()));
+                }
+                if match_str.contains("denied") {
+                    return Err(anyhow!(
+                        "Permission denied (Wrong password/key?):\n{}",
+                        log.dump()
+                    ));
                 }
             },
             Err(expectrl::Error::ExpectTimeout) => {
-                // --- SUCCESS CHECK ---
-                // The expect timed out. This means SSH is silent.
-                // If the process is still running, it means the connection is likely established.
-                if is_process_alive(&mut p) {
-                    info!("Tunnel established (Silent Mode). SOCKS5: 127.0.0.1:{}", local_port);
-                    break; // Exit the interaction loop, move to monitoring
-                } else {
-                    return Err(anyhow!("SSH process died unexpectedly during initialization."));
+                if !is_process_alive(&mut p) {
+                    return Err(anyhow!(
+                        "SSH process died unexpectedly during initialization:\n{}",
+                        log.dump()
+                    ));
+                }
+
+                silent_beats += 1;
+                if silent_beats >= MAX_SILENT_BEATS {
+                    return Err(anyhow!(
+                        "No confirmation of tunnel establishment after {}s:\n{}",
+                        MAX_SILENT_BEATS * 5,
+                        log.dump()
+                    ));
                 }
+                debug!("No state transition yet, still waiting ({}/{})", silent_beats, MAX_SILENT_BEATS);
             },
             Err(e) => {
-                return Err(anyhow!("Interaction error: {}", e));
+                return Err(anyhow!("Interaction error: {}\n{}", e, log.dump()));
             }
         }
     }